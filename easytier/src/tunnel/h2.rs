@@ -0,0 +1,400 @@
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use anyhow::Context as _;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{Sink, Stream};
+use h2::{RecvStream, SendStream};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use zerocopy::AsBytes;
+
+use crate::rpc::TunnelInfo;
+
+use super::{
+    common::{setup_sokcet2, TunnelWrapper},
+    insecure_tls::{
+        get_insecure_tls_cert, get_insecure_tls_client_config, get_verifying_tls_client_config,
+        init_crypto_provider, TlsTrustSource,
+    },
+    packet_def::{ZCPacket, ZCPacketType},
+    FromUrl, IpVersion, Tunnel, TunnelConnector, TunnelError, TunnelListener,
+};
+
+/// Every `ZCPacket` on the wire is framed as a 4-byte big-endian length
+/// prefix followed by its payload. Unlike the WebSocket transport, which
+/// gets message boundaries for free from the upgrade protocol, an H2
+/// stream is just a byte stream and needs explicit framing.
+const LEN_PREFIX_BYTES: usize = 4;
+
+/// Largest payload a framed `DATA` chunk is allowed to declare. An h2/h2s
+/// listener can be reached by untrusted/middlebox-exposed traffic, so the
+/// length prefix can't be trusted unconditionally: a peer claiming a
+/// multi-gigabyte frame would otherwise make us buffer that much memory
+/// before noticing anything is wrong.
+const MAX_FRAME_PAYLOAD_BYTES: usize = 8 << 20;
+
+fn is_h2s(addr: &url::Url) -> Result<bool, TunnelError> {
+    match addr.scheme() {
+        "h2" => Ok(false),
+        "h2s" => Ok(true),
+        _ => Err(TunnelError::InvalidProtocol(addr.scheme().to_string())),
+    }
+}
+
+fn frame_zc_packet(msg: ZCPacket) -> Bytes {
+    let payload = msg.tunnel_payload_bytes().freeze();
+    let mut framed = BytesMut::with_capacity(LEN_PREFIX_BYTES + payload.len());
+    framed.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    framed.extend_from_slice(&payload);
+    framed.freeze()
+}
+
+/// Turns a raw H2 body stream into a stream of framed `ZCPacket`s,
+/// buffering partial frames across `DATA` chunks.
+fn packet_stream_from_recv_stream(
+    body: RecvStream,
+) -> impl Stream<Item = Result<ZCPacket, TunnelError>> {
+    futures::stream::unfold((body, BytesMut::new()), |(mut body, mut buf)| async move {
+        loop {
+            if buf.len() >= LEN_PREFIX_BYTES {
+                let len = u32::from_be_bytes(buf[..LEN_PREFIX_BYTES].try_into().unwrap()) as usize;
+                if len > MAX_FRAME_PAYLOAD_BYTES {
+                    return Some((
+                        Err(TunnelError::InvalidPacket(format!(
+                            "h2 frame length {} exceeds max of {} bytes",
+                            len, MAX_FRAME_PAYLOAD_BYTES
+                        ))),
+                        (body, buf),
+                    ));
+                }
+                if buf.len() >= LEN_PREFIX_BYTES + len {
+                    let mut frame = buf.split_to(LEN_PREFIX_BYTES + len);
+                    let payload = frame.split_off(LEN_PREFIX_BYTES);
+                    let packet = ZCPacket::new_from_buf(payload, ZCPacketType::DummyTunnel);
+                    return Some((Ok(packet), (body, buf)));
+                }
+            }
+
+            match body.data().await {
+                Some(Ok(chunk)) => {
+                    let _ = body.flow_control().release_capacity(chunk.len());
+                    buf.extend_from_slice(chunk.chunk());
+                }
+                Some(Err(e)) => {
+                    tracing::error!(?e, "recv from h2 stream error");
+                    return Some((Err(TunnelError::from(anyhow::Error::new(e))), (body, buf)));
+                }
+                None => return None,
+            }
+        }
+    })
+}
+
+/// Adapts an H2 `SendStream<Bytes>` (which only exposes `send_data`/
+/// `poll_capacity`) into a `futures::Sink<ZCPacket>`, matching the
+/// `TunnelWrapper` write-half shape used by the WebSocket transport.
+struct H2PacketSink {
+    send_stream: SendStream<Bytes>,
+}
+
+impl Sink<ZCPacket> for H2PacketSink {
+    type Error = TunnelError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.send_stream.reserve_capacity(1);
+        match self.send_stream.poll_capacity(cx) {
+            Poll::Ready(Some(Ok(_))) => Poll::Ready(Ok(())),
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Err(TunnelError::from(anyhow::Error::new(e)))),
+            Poll::Ready(None) => Poll::Ready(Err(TunnelError::InvalidPacket(
+                "h2 send stream closed".to_owned(),
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: ZCPacket) -> Result<(), Self::Error> {
+        self.send_stream
+            .send_data(frame_zc_packet(item), false)
+            .map_err(|e| TunnelError::from(anyhow::Error::new(e)))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        let _ = self.send_stream.send_data(Bytes::new(), true);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[derive(Debug)]
+pub struct H2TunnelListener {
+    addr: url::Url,
+    listener: Option<TcpListener>,
+}
+
+impl H2TunnelListener {
+    pub fn new(addr: url::Url) -> Self {
+        H2TunnelListener {
+            addr,
+            listener: None,
+        }
+    }
+
+    async fn try_accept(&mut self, stream: TcpStream) -> Result<Box<dyn Tunnel>, TunnelError> {
+        let info = TunnelInfo {
+            tunnel_type: self.addr.scheme().to_owned(),
+            local_addr: self.local_url().into(),
+            remote_addr: super::build_url_from_socket_addr(
+                &stream.peer_addr()?.to_string(),
+                self.addr.scheme().to_string().as_str(),
+            )
+            .into(),
+        };
+
+        async fn take_first_stream<T>(
+            mut connection: h2::server::Connection<T, Bytes>,
+        ) -> Result<(RecvStream, SendStream<Bytes>), TunnelError>
+        where
+            T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+        {
+            let (request, mut respond) = connection
+                .accept()
+                .await
+                .ok_or_else(|| {
+                    TunnelError::InvalidPacket("h2 client never opened a stream".to_owned())
+                })?
+                .with_context(|| "h2 accept failed")?;
+
+            let response = http::Response::new(());
+            let send_stream = respond
+                .send_response(response, false)
+                .with_context(|| "failed to send h2 response")?;
+
+            // Keep driving the connection in the background so flow control
+            // and further frames for the accepted stream make progress; we
+            // only ever expect the one long-lived tunnel stream.
+            tokio::spawn(async move { while connection.accept().await.is_some() {} });
+
+            Ok((request.into_body(), send_stream))
+        }
+
+        let (recv_stream, send_stream) = if is_h2s(&self.addr)? {
+            init_crypto_provider();
+            let (certs, key) = get_insecure_tls_cert();
+            let config = rustls::ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .with_context(|| "Failed to create server config")?;
+            let acceptor = TlsAcceptor::from(Arc::new(config));
+            let stream = acceptor.accept(stream).await?;
+            let connection = h2::server::handshake(stream)
+                .await
+                .with_context(|| "h2 server handshake failed")?;
+            take_first_stream(connection).await?
+        } else {
+            let connection = h2::server::handshake(stream)
+                .await
+                .with_context(|| "h2 server handshake failed")?;
+            take_first_stream(connection).await?
+        };
+
+        let read = packet_stream_from_recv_stream(recv_stream);
+        let write = H2PacketSink { send_stream };
+
+        Ok(Box::new(TunnelWrapper::new(read, write, Some(info))))
+    }
+}
+
+#[async_trait::async_trait]
+impl TunnelListener for H2TunnelListener {
+    async fn listen(&mut self) -> Result<(), TunnelError> {
+        let addr = SocketAddr::from_url(self.addr.clone(), IpVersion::Both)?;
+        let socket2_socket = socket2::Socket::new(
+            socket2::Domain::for_address(addr),
+            socket2::Type::STREAM,
+            Some(socket2::Protocol::TCP),
+        )?;
+        setup_sokcet2(&socket2_socket, &addr)?;
+        let socket = TcpSocket::from_std_stream(socket2_socket.into());
+
+        self.addr
+            .set_port(Some(socket.local_addr()?.port()))
+            .unwrap();
+
+        self.listener = Some(socket.listen(1024)?);
+        Ok(())
+    }
+
+    async fn accept(&mut self) -> Result<Box<dyn Tunnel>, TunnelError> {
+        loop {
+            let listener = self.listener.as_ref().unwrap();
+            let (stream, _) = listener.accept().await?;
+            stream.set_nodelay(true).unwrap();
+            match self.try_accept(stream).await {
+                Ok(tunnel) => return Ok(tunnel),
+                Err(e) => {
+                    tracing::error!(?e, ?self, "Failed to accept h2/h2s tunnel");
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn local_url(&self) -> url::Url {
+        self.addr.clone()
+    }
+}
+
+pub struct H2TunnelConnector {
+    addr: url::Url,
+    ip_version: IpVersion,
+    insecure_tls: bool,
+    tls_trust_source: TlsTrustSource,
+}
+
+impl H2TunnelConnector {
+    pub fn new(addr: url::Url) -> Self {
+        H2TunnelConnector {
+            addr,
+            ip_version: IpVersion::Both,
+            insecure_tls: false,
+            tls_trust_source: TlsTrustSource::default(),
+        }
+    }
+
+    pub fn set_insecure_tls(&mut self, insecure: bool) -> &mut Self {
+        self.insecure_tls = insecure;
+        self
+    }
+
+    pub fn set_tls_trust_source(&mut self, trust: TlsTrustSource) -> &mut Self {
+        self.tls_trust_source = trust;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl TunnelConnector for H2TunnelConnector {
+    async fn connect(&mut self) -> Result<Box<dyn Tunnel>, TunnelError> {
+        let is_h2s = is_h2s(&self.addr)?;
+        let addr = SocketAddr::from_url(self.addr.clone(), self.ip_version)?;
+        let local_addr = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+
+        let info = TunnelInfo {
+            tunnel_type: self.addr.scheme().to_owned(),
+            local_addr: super::build_url_from_socket_addr(
+                &local_addr.to_string(),
+                self.addr.scheme().to_string().as_str(),
+            )
+            .into(),
+            remote_addr: self.addr.to_string(),
+        };
+
+        let socket2_socket = socket2::Socket::new(
+            socket2::Domain::for_address(addr),
+            socket2::Type::STREAM,
+            Some(socket2::Protocol::TCP),
+        )?;
+        setup_sokcet2(&socket2_socket, &addr)?;
+        let socket = TcpSocket::from_std_stream(socket2_socket.into());
+        let stream = socket.connect(addr).await?;
+        stream.set_nodelay(true).unwrap();
+
+        let (send_request, connection) = if is_h2s {
+            init_crypto_provider();
+            let client_config = if self.insecure_tls {
+                get_insecure_tls_client_config()
+            } else {
+                get_verifying_tls_client_config(&self.tls_trust_source)
+                    .with_context(|| "failed to build verifying tls client config")?
+            };
+            let host = self
+                .addr
+                .host_str()
+                .ok_or_else(|| TunnelError::InvalidProtocol("h2s url has no host".to_owned()))?
+                .to_owned();
+            let server_name = rustls::pki_types::ServerName::try_from(host)
+                .with_context(|| "invalid server name for h2s SNI")?;
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+            let stream = connector
+                .connect(server_name, stream)
+                .await
+                .with_context(|| "h2s tls handshake failed")?;
+            h2::client::handshake(stream)
+                .await
+                .with_context(|| "h2 client handshake failed")?
+        } else {
+            h2::client::handshake(stream)
+                .await
+                .with_context(|| "h2 client handshake failed")?
+        };
+
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::warn!(?e, "h2 connection driver exited");
+            }
+        });
+
+        let mut send_request = send_request
+            .ready()
+            .await
+            .with_context(|| "h2 send_request not ready")?;
+
+        let request = http::Request::builder()
+            .method(http::Method::POST)
+            .uri(self.addr.path().to_owned())
+            .body(())
+            .with_context(|| "failed to build h2 request")?;
+        let (response_fut, send_stream) = send_request
+            .send_request(request, false)
+            .with_context(|| "failed to send h2 request")?;
+        let response = response_fut.await.with_context(|| "h2 response failed")?;
+
+        let read = packet_stream_from_recv_stream(response.into_body());
+        let write = H2PacketSink { send_stream };
+
+        Ok(Box::new(TunnelWrapper::new(read, write, Some(info))))
+    }
+
+    fn remote_url(&self) -> url::Url {
+        self.addr.clone()
+    }
+
+    fn set_ip_version(&mut self, ip_version: IpVersion) {
+        self.ip_version = ip_version;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tunnel::common::tests::_tunnel_pingpong;
+    use crate::tunnel::h2::{H2TunnelConnector, H2TunnelListener};
+
+    #[rstest::rstest]
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn h2_pingpong(#[values("h2", "h2s")] proto: &str) {
+        let listener = H2TunnelListener::new(format!("{}://0.0.0.0:25560", proto).parse().unwrap());
+        let mut connector =
+            H2TunnelConnector::new(format!("{}://127.0.0.1:25560", proto).parse().unwrap());
+        // only the h2s case actually negotiates TLS, and its listener cert
+        // is self-signed, so insecure_tls is set for both protos; the plain
+        // h2 case ignores it.
+        connector.set_insecure_tls(true);
+        _tunnel_pingpong(listener, connector).await
+    }
+}
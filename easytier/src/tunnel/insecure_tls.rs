@@ -0,0 +1,158 @@
+use std::{path::PathBuf, sync::Arc, sync::Once};
+
+use anyhow::Context;
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+
+static CRYPTO_PROVIDER_INIT: Once = Once::new();
+
+/// Installs the process-wide default `rustls` crypto provider. Safe to call
+/// repeatedly; only the first call has any effect.
+pub fn init_crypto_provider() {
+    CRYPTO_PROVIDER_INIT.call_once(|| {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+    });
+}
+
+#[derive(Debug)]
+struct NoServerCertVerifier;
+
+impl ServerCertVerifier for NoServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        vec![
+            SignatureScheme::RSA_PKCS1_SHA256,
+            SignatureScheme::ECDSA_NISTP256_SHA256,
+            SignatureScheme::RSA_PSS_SHA256,
+            SignatureScheme::ED25519,
+        ]
+    }
+}
+
+/// Builds a `ClientConfig` that accepts any server certificate without
+/// verification. Used when the caller explicitly opts into `--insecure` /
+/// skip-verify behavior, or for the self-signed cert pair returned by
+/// [`get_insecure_tls_cert`].
+pub fn get_insecure_tls_client_config() -> ClientConfig {
+    ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(NoServerCertVerifier))
+        .with_no_client_auth()
+}
+
+/// Returns a self-signed cert/key pair for `wss://` listeners that were not
+/// configured with a real certificate. Purely for encryption, not
+/// authentication; clients must use [`get_insecure_tls_client_config`] (or
+/// an equivalent skip-verify mode) to connect to it.
+pub fn get_insecure_tls_cert() -> (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>) {
+    let cert = rcgen::generate_simple_self_signed(vec!["easytier.tunnel".to_string()])
+        .expect("failed to generate self-signed cert");
+    let cert_der = CertificateDer::from(cert.cert);
+    let key_der = PrivateKeyDer::try_from(cert.signing_key.serialize_der())
+        .expect("failed to encode self-signed key");
+    (vec![cert_der], key_der)
+}
+
+/// Where a verifying `wss://` client should source its trust anchors from.
+#[derive(Debug, Clone, Default)]
+pub enum TlsTrustSource {
+    /// Use the OS-provided trust store via `rustls-native-certs`.
+    #[default]
+    SystemRoots,
+    /// Use the bundled Mozilla root set via `webpki-roots`, avoiding any
+    /// filesystem access to the OS trust store.
+    WebPkiRoots,
+    /// Trust only the CA certificates found in the given PEM file.
+    CaFile(PathBuf),
+}
+
+fn load_root_store(trust: &TlsTrustSource) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    match trust {
+        TlsTrustSource::SystemRoots => {
+            let native = rustls_native_certs::load_native_certs();
+            for err in &native.errors {
+                tracing::warn!(?err, "failed to load a native cert");
+            }
+            for cert in native.certs {
+                if let Err(e) = roots.add(cert) {
+                    tracing::warn!(?e, "failed to trust a native cert");
+                }
+            }
+        }
+        TlsTrustSource::WebPkiRoots => {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+        TlsTrustSource::CaFile(path) => {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("failed to read CA file: {:?}", path))?;
+            let mut reader = std::io::BufReader::new(pem.as_slice());
+            for cert in rustls_pemfile::certs(&mut reader) {
+                roots
+                    .add(cert.with_context(|| format!("invalid CA cert in {:?}", path))?)
+                    .with_context(|| format!("failed to trust CA cert in {:?}", path))?;
+            }
+        }
+    }
+    Ok(roots)
+}
+
+/// Builds a `ClientConfig` that verifies the server certificate against
+/// `trust` using a real `WebPkiServerVerifier`, with SNI derived from the
+/// connect URL's host by the caller.
+pub fn get_verifying_tls_client_config(trust: &TlsTrustSource) -> anyhow::Result<ClientConfig> {
+    let roots = load_root_store(trust)?;
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifying_client_config_builds_with_webpki_roots() {
+        init_crypto_provider();
+        get_verifying_tls_client_config(&TlsTrustSource::WebPkiRoots).unwrap();
+    }
+
+    #[test]
+    fn verifying_client_config_errs_on_missing_ca_file() {
+        init_crypto_provider();
+        let missing = PathBuf::from("/nonexistent/easytier-test-ca.pem");
+        let err = get_verifying_tls_client_config(&TlsTrustSource::CaFile(missing)).unwrap_err();
+        assert!(format!("{:?}", err).contains("failed to read CA file"));
+    }
+}
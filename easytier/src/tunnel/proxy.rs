@@ -0,0 +1,263 @@
+use anyhow::Context;
+use base64::Engine;
+#[cfg(test)]
+use tokio::net::TcpListener;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::TunnelError;
+
+/// An upstream proxy `WSTunnelConnector::connect` should egress through
+/// before handing the stream off to TLS / the WebSocket client builder.
+#[derive(Debug, Clone)]
+pub enum ProxyConfig {
+    /// `http://[user:pass@]host:port`, using an HTTP `CONNECT` with
+    /// optional Basic auth.
+    HttpConnect(url::Url),
+    /// `socks5://[user:pass@]host:port`.
+    Socks5(url::Url),
+}
+
+impl ProxyConfig {
+    pub fn from_url(url: url::Url) -> Result<Self, TunnelError> {
+        match url.scheme() {
+            "http" => Ok(ProxyConfig::HttpConnect(url)),
+            "socks5" => Ok(ProxyConfig::Socks5(url)),
+            other => Err(TunnelError::InvalidProtocol(format!(
+                "unsupported proxy scheme: {}",
+                other
+            ))),
+        }
+    }
+
+    /// The proxy's own address, to be dialed directly (never through
+    /// itself).
+    pub fn proxy_url(&self) -> &url::Url {
+        match self {
+            ProxyConfig::HttpConnect(url) => url,
+            ProxyConfig::Socks5(url) => url,
+        }
+    }
+}
+
+/// Performs the proxy handshake on an already-connected TCP stream to the
+/// proxy, leaving `stream` ready to carry the target protocol (TLS or
+/// plaintext WebSocket upgrade) as if connected directly to
+/// `target_host:target_port`.
+pub async fn handshake(
+    proxy: &ProxyConfig,
+    stream: &mut TcpStream,
+    target_host: &str,
+    target_port: u16,
+) -> Result<(), TunnelError> {
+    match proxy {
+        ProxyConfig::HttpConnect(url) => {
+            http_connect_handshake(url, stream, target_host, target_port).await
+        }
+        ProxyConfig::Socks5(url) => socks5_handshake(url, stream, target_host, target_port).await,
+    }
+}
+
+async fn http_connect_handshake(
+    proxy_url: &url::Url,
+    stream: &mut TcpStream,
+    target_host: &str,
+    target_port: u16,
+) -> Result<(), TunnelError> {
+    let mut request = format!(
+        "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
+        host = target_host,
+        port = target_port
+    );
+    if !proxy_url.username().is_empty() {
+        let creds = format!(
+            "{}:{}",
+            proxy_url.username(),
+            proxy_url.password().unwrap_or("")
+        );
+        let encoded = base64::engine::general_purpose::STANDARD.encode(creds);
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", encoded));
+    }
+    request.push_str("\r\n");
+
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .with_context(|| "failed to send CONNECT request to proxy")?;
+
+    // Read the response head one byte at a time up to the blank line, so
+    // nothing past it is consumed: once CONNECT succeeds the stream is a
+    // raw byte pipe the target protocol takes over from here.
+    let mut response = Vec::with_capacity(256);
+    let mut byte = [0u8; 1];
+    loop {
+        stream
+            .read_exact(&mut byte)
+            .await
+            .with_context(|| "proxy closed connection before CONNECT response")?;
+        response.push(byte[0]);
+        if response.ends_with(b"\r\n\r\n") {
+            break;
+        }
+        if response.len() > 8192 {
+            return Err(TunnelError::InvalidPacket(
+                "CONNECT response from proxy too large".to_owned(),
+            ));
+        }
+    }
+
+    let status_line = response.split(|&b| b == b'\n').next().unwrap_or_default();
+    let status_line = String::from_utf8_lossy(status_line);
+    if !status_line.contains(" 200 ") {
+        return Err(TunnelError::InvalidPacket(format!(
+            "proxy CONNECT rejected: {}",
+            status_line.trim()
+        )));
+    }
+
+    Ok(())
+}
+
+async fn socks5_handshake(
+    proxy_url: &url::Url,
+    stream: &mut TcpStream,
+    target_host: &str,
+    target_port: u16,
+) -> Result<(), TunnelError> {
+    if target_host.len() > u8::MAX as usize {
+        return Err(TunnelError::InvalidProtocol(format!(
+            "socks5 target host too long ({} bytes, max {})",
+            target_host.len(),
+            u8::MAX
+        )));
+    }
+
+    let has_auth = !proxy_url.username().is_empty();
+    let methods: &[u8] = if has_auth { &[0x00, 0x02] } else { &[0x00] };
+    let mut greeting = vec![0x05, methods.len() as u8];
+    greeting.extend_from_slice(methods);
+    stream
+        .write_all(&greeting)
+        .await
+        .with_context(|| "failed to send socks5 greeting")?;
+
+    let mut chosen = [0u8; 2];
+    stream
+        .read_exact(&mut chosen)
+        .await
+        .with_context(|| "failed to read socks5 greeting reply")?;
+    if chosen[0] != 0x05 {
+        return Err(TunnelError::InvalidPacket(
+            "proxy is not a socks5 server".to_owned(),
+        ));
+    }
+
+    match chosen[1] {
+        0x00 => {}
+        0x02 => {
+            let user = proxy_url.username().as_bytes();
+            let pass = proxy_url.password().unwrap_or("").as_bytes();
+            let mut auth = vec![0x01, user.len() as u8];
+            auth.extend_from_slice(user);
+            auth.push(pass.len() as u8);
+            auth.extend_from_slice(pass);
+            stream
+                .write_all(&auth)
+                .await
+                .with_context(|| "failed to send socks5 credentials")?;
+
+            let mut auth_reply = [0u8; 2];
+            stream
+                .read_exact(&mut auth_reply)
+                .await
+                .with_context(|| "failed to read socks5 auth reply")?;
+            if auth_reply[1] != 0x00 {
+                return Err(TunnelError::InvalidPacket(
+                    "socks5 proxy rejected credentials".to_owned(),
+                ));
+            }
+        }
+        0xff => {
+            return Err(TunnelError::InvalidPacket(
+                "socks5 proxy has no acceptable auth method".to_owned(),
+            ))
+        }
+        other => {
+            return Err(TunnelError::InvalidPacket(format!(
+                "socks5 proxy selected unsupported auth method: {}",
+                other
+            )))
+        }
+    }
+
+    let mut request = vec![0x05, 0x01, 0x00, 0x03, target_host.len() as u8];
+    request.extend_from_slice(target_host.as_bytes());
+    request.extend_from_slice(&target_port.to_be_bytes());
+    stream
+        .write_all(&request)
+        .await
+        .with_context(|| "failed to send socks5 connect request")?;
+
+    let mut reply_head = [0u8; 4];
+    stream
+        .read_exact(&mut reply_head)
+        .await
+        .with_context(|| "failed to read socks5 connect reply")?;
+    if reply_head[1] != 0x00 {
+        return Err(TunnelError::InvalidPacket(format!(
+            "socks5 proxy refused CONNECT, reply code {}",
+            reply_head[1]
+        )));
+    }
+
+    // Drain the bound address the proxy echoes back; its contents don't
+    // matter to us, but the bytes must still be consumed from the stream.
+    let addr_len = match reply_head[3] {
+        0x01 => 4,
+        0x03 => {
+            let mut len = [0u8; 1];
+            stream
+                .read_exact(&mut len)
+                .await
+                .with_context(|| "failed to read socks5 bound domain length")?;
+            len[0] as usize
+        }
+        0x04 => 16,
+        other => {
+            return Err(TunnelError::InvalidPacket(format!(
+                "socks5 proxy returned unknown address type {}",
+                other
+            )))
+        }
+    };
+    let mut rest = vec![0u8; addr_len + 2];
+    stream
+        .read_exact(&mut rest)
+        .await
+        .with_context(|| "failed to read socks5 bound address")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn socks5_rejects_oversized_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accept = tokio::spawn(async move { listener.accept().await.unwrap() });
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let _server = accept.await.unwrap();
+
+        let proxy_url = url::Url::parse("socks5://127.0.0.1:1080").unwrap();
+        let target_host = "a".repeat(256);
+        let err = socks5_handshake(&proxy_url, &mut stream, &target_host, 443)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TunnelError::InvalidProtocol(_)));
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+use super::Tunnel;
+
+/// An idle, already-upgraded ws/wss connection waiting to be handed out by
+/// [`WsConnectionPool::acquire`]. Kept alive but unpolled until claimed.
+struct IdleEntry {
+    tunnel: Box<dyn Tunnel>,
+    idle_since: Instant,
+}
+
+/// Pool of pre-dialed, pre-upgraded ws/wss connections to a single remote
+/// URL, so `WSTunnelConnector::connect` can skip the TCP + TLS + upgrade
+/// round trip when a spare connection is sitting idle.
+///
+/// An idle entry is never polled, so there's no cheap way to tell a peer
+/// that reset the connection while it sat unused from one that's still
+/// alive. Rather than pretend otherwise, a connection is simply treated as
+/// dead once it has sat idle longer than `idle_timeout`; callers that pool
+/// aggressively should keep `idle_timeout` short relative to how long their
+/// peers tend to hold idle sockets open.
+#[derive(Debug)]
+pub struct WsConnectionPool {
+    max_idle: usize,
+    idle_timeout: Duration,
+    idle: Mutex<VecDeque<IdleEntry>>,
+}
+
+impl std::fmt::Debug for IdleEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdleEntry")
+            .field("idle_since", &self.idle_since)
+            .finish()
+    }
+}
+
+impl WsConnectionPool {
+    pub fn new(max_idle: usize, idle_timeout: Duration) -> Self {
+        WsConnectionPool {
+            max_idle,
+            idle_timeout,
+            idle: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn max_idle(&self) -> usize {
+        self.max_idle
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_idle > 0
+    }
+
+    /// Pops the first non-expired idle connection, discarding any stale
+    /// ones along the way.
+    pub async fn acquire(&self) -> Option<Box<dyn Tunnel>> {
+        let mut idle = self.idle.lock().await;
+        while let Some(entry) = idle.pop_front() {
+            if entry.idle_since.elapsed() > self.idle_timeout {
+                tracing::debug!("dropping expired idle ws connection");
+                continue;
+            }
+            return Some(entry.tunnel);
+        }
+        None
+    }
+
+    /// Offers a freshly-dialed, not-yet-used connection to the pool.
+    /// Dropped immediately if the pool is already at capacity.
+    pub async fn offer(&self, tunnel: Box<dyn Tunnel>) {
+        let mut idle = self.idle.lock().await;
+        if idle.len() >= self.max_idle {
+            return;
+        }
+        idle.push_back(IdleEntry {
+            tunnel,
+            idle_since: Instant::now(),
+        });
+    }
+
+    /// Number of connections currently parked in the pool, live or not.
+    pub async fn len(&self) -> usize {
+        self.idle.lock().await.len()
+    }
+
+    pub fn needs_refill(&self, current_len: usize) -> bool {
+        self.is_enabled() && current_len < self.max_idle
+    }
+}